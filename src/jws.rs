@@ -0,0 +1,85 @@
+use anyhow::Result;
+use ssi::{
+    jwk::{Algorithm, JWK},
+    jws::{sign_bytes, verify_bytes},
+};
+
+/// The signing algorithms this crate actually supports, kept as an explicit,
+/// enumerable type rather than leaning on `JWK::algorithm` (which is absent
+/// unless a key source happens to set it) or on `ssi`'s much larger
+/// `Algorithm`. Every caller gets the same "unsupported algorithm" error
+/// path, and the "pick the right curve/hash" logic is shared by every
+/// `AuthorizationToken` that verifies a JWS, Tezos-signed or not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JwsSignatureAlgorithm {
+    /// Ed25519 over Blake2b, as used by Tezos tz1 (edpk/edsig) keys.
+    EdBlake2b,
+    /// secp256k1 over Blake2b, as used by Tezos tz2 (sppk/spsig) keys.
+    ESBlake2bK,
+    /// P-256 over Blake2b, as used by Tezos tz3 (p2pk/p2sig) keys.
+    ESBlake2b,
+    /// P-256 over SHA-256, the plain JOSE `ES256`.
+    ES256,
+    /// Plain Ed25519 JOSE `EdDSA`, as used by `did:key` issuers.
+    EdDSA,
+}
+
+impl JwsSignatureAlgorithm {
+    /// Selects the algorithm from a Tezos base58check public key prefix:
+    /// Tezos encodes the curve into the prefix itself (edpk/sppk/p2pk), so
+    /// this never needs to decode the key.
+    pub fn from_tz_pk(pk: &str) -> Result<Self> {
+        if pk.starts_with("edpk") {
+            Ok(Self::EdBlake2b)
+        } else if pk.starts_with("sppk") {
+            Ok(Self::ESBlake2bK)
+        } else if pk.starts_with("p2pk") {
+            Ok(Self::ESBlake2b)
+        } else {
+            Err(anyhow!("Unrecognised Tezos public key prefix"))
+        }
+    }
+
+    /// Selects the algorithm from a `did:key` multicodec prefix.
+    pub fn from_did_key_codec(codec: u8) -> Result<Self> {
+        match codec {
+            0xed => Ok(Self::EdDSA),
+            other => Err(anyhow!("Unsupported did:key codec: 0x{:x}", other)),
+        }
+    }
+
+    fn as_ssi_algorithm(self) -> Algorithm {
+        match self {
+            Self::EdBlake2b => Algorithm::EdBlake2b,
+            Self::ESBlake2bK => Algorithm::ESBlake2bK,
+            Self::ESBlake2b => Algorithm::ESBlake2b,
+            Self::ES256 => Algorithm::ES256,
+            Self::EdDSA => Algorithm::EdDSA,
+        }
+    }
+
+    pub fn verify(self, data: &[u8], key: &JWK, signature: &[u8]) -> Result<()> {
+        Ok(verify_bytes(self.as_ssi_algorithm(), data, key, signature)?)
+    }
+
+    pub fn sign(self, data: &[u8], key: &JWK) -> Result<Vec<u8>> {
+        Ok(sign_bytes(self.as_ssi_algorithm(), data, key)?)
+    }
+}
+
+#[test]
+fn tz_prefix_selection() {
+    assert_eq!(
+        JwsSignatureAlgorithm::from_tz_pk("edpkurFSehqm2HhLP9sZ4ZRW5nLZgyWErW8wYxgEUPHCMCy6Hk1tbm").unwrap(),
+        JwsSignatureAlgorithm::EdBlake2b
+    );
+    assert_eq!(
+        JwsSignatureAlgorithm::from_tz_pk("sppk7bSAFdAMDi4NrRn2jdokmzh9j3QUzWLLK2SY4EsyMcCcF8kFAWp").unwrap(),
+        JwsSignatureAlgorithm::ESBlake2bK
+    );
+    assert_eq!(
+        JwsSignatureAlgorithm::from_tz_pk("p2pk67jx7bwg8GJyLMRe6cw2HVPhmzbqYvoKbckBz9AYD4REP4AYYcQ").unwrap(),
+        JwsSignatureAlgorithm::ESBlake2b
+    );
+    assert!(JwsSignatureAlgorithm::from_tz_pk("notakey").is_err());
+}
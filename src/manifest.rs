@@ -0,0 +1,39 @@
+use crate::{replay::ReplayGuard, tzkt::DidTzResolver};
+use ssi::did::DIDURL;
+use std::collections::HashSet;
+
+/// Per-orbit configuration for the optional on-chain controller resolver and
+/// replay cache used by [`crate::tz`]'s `AuthorizationPolicy` impl. Both are
+/// `None` by default, preserving the original locally-derived, no-replay-
+/// protection behaviour for orbits that don't opt in.
+#[derive(Default)]
+pub struct ManifestConfig {
+    pub tzkt_resolver: Option<DidTzResolver>,
+    pub replay_guard: Option<ReplayGuard>,
+}
+
+/// An orbit's manifest: the set of DIDs allowed to invoke it, plus whatever
+/// optional authorization config (on-chain resolution, replay protection)
+/// the orbit was provisioned with.
+pub struct Manifest {
+    invokers: HashSet<DIDURL>,
+    config: ManifestConfig,
+}
+
+impl Manifest {
+    pub fn new(invokers: HashSet<DIDURL>, config: ManifestConfig) -> Self {
+        Self { invokers, config }
+    }
+
+    pub fn invokers(&self) -> &HashSet<DIDURL> {
+        &self.invokers
+    }
+
+    pub fn tzkt_resolver(&self) -> Option<&DidTzResolver> {
+        self.config.tzkt_resolver.as_ref()
+    }
+
+    pub fn replay_guard(&self) -> Option<&ReplayGuard> {
+        self.config.replay_guard.as_ref()
+    }
+}
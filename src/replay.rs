@@ -0,0 +1,72 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use std::{collections::HashMap, sync::RwLock};
+
+/// Rejects a previously-seen signature within a configurable freshness
+/// window, closing the replay hole left by a `timestamp` that is signed over
+/// but never checked. Bounded both by `max_age` (anything older is already
+/// stale and pruned on the next check) and `capacity` (oldest entries are
+/// evicted first if the cache fills before they age out).
+pub struct ReplayGuard {
+    max_age: Duration,
+    capacity: usize,
+    seen: RwLock<HashMap<String, DateTime<Utc>>>,
+}
+
+impl ReplayGuard {
+    pub fn new(max_age: Duration, capacity: usize) -> Self {
+        Self {
+            max_age,
+            capacity,
+            seen: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Checks that `timestamp` is within `max_age` of `now` and that
+    /// `signature` has not already been presented within the window.
+    /// Records the signature as seen on success.
+    pub fn check(&self, signature: &str, timestamp: DateTime<Utc>, now: DateTime<Utc>) -> Result<()> {
+        let age = now.signed_duration_since(timestamp);
+        if age > self.max_age || age < -self.max_age {
+            return Err(anyhow!("Timestamp outside freshness window"));
+        }
+
+        let mut seen = self.seen.write().unwrap();
+        seen.retain(|_, t| now.signed_duration_since(*t) <= self.max_age);
+        if seen.contains_key(signature) {
+            return Err(anyhow!("Signature already used"));
+        }
+        if seen.len() >= self.capacity {
+            if let Some(oldest) = seen.iter().min_by_key(|(_, t)| **t).map(|(k, _)| k.clone()) {
+                seen.remove(&oldest);
+            }
+        }
+        seen.insert(signature.to_string(), now);
+        Ok(())
+    }
+}
+
+#[test]
+fn rejects_stale_timestamp() {
+    let guard = ReplayGuard::new(Duration::minutes(5), 100);
+    let now = Utc::now();
+    assert!(guard.check("sig-a", now - Duration::minutes(10), now).is_err());
+}
+
+#[test]
+fn rejects_replayed_signature() {
+    let guard = ReplayGuard::new(Duration::minutes(5), 100);
+    let now = Utc::now();
+    assert!(guard.check("sig-a", now, now).is_ok());
+    assert!(guard.check("sig-a", now, now).is_err());
+}
+
+#[test]
+fn evicts_oldest_once_full() {
+    let guard = ReplayGuard::new(Duration::minutes(5), 1);
+    let now = Utc::now();
+    assert!(guard.check("sig-a", now, now).is_ok());
+    assert!(guard.check("sig-b", now, now + Duration::seconds(1)).is_ok());
+    // sig-a was evicted to make room for sig-b, so it can be seen again.
+    assert!(guard.check("sig-a", now, now + Duration::seconds(2)).is_ok());
+}
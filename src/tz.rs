@@ -1,9 +1,11 @@
 use crate::{
     auth::{Action, AuthorizationPolicy, AuthorizationToken},
+    jws::JwsSignatureAlgorithm,
     manifest::Manifest,
     resource::OrbitId,
 };
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use nom::{
     branch::alt,
     bytes::complete::{tag, take_until},
@@ -14,7 +16,6 @@ use nom::{
 use rocket::request::{FromRequest, Outcome, Request};
 use ssi::{
     did::DIDURL,
-    jws::verify_bytes,
     tzkey::{decode_tzsig, jwk_from_tezos_key},
 };
 use std::str::FromStr;
@@ -147,13 +148,11 @@ impl TezosAuthorizationString {
     fn verify(&self) -> Result<()> {
         let key = jwk_from_tezos_key(&self.pk)?;
         let (_, sig) = decode_tzsig(&self.sig)?;
-        Ok(verify_bytes(
-            key.algorithm
-                .ok_or_else(|| anyhow!("Invalid Signature Scheme"))?,
+        JwsSignatureAlgorithm::from_tz_pk(&self.pk)?.verify(
             &self.serialize_for_verification()?,
             &key,
             &sig,
-        )?)
+        )
     }
 }
 
@@ -212,17 +211,60 @@ impl core::fmt::Display for TezosAuthorizationString {
 #[rocket::async_trait]
 impl AuthorizationPolicy<TezosAuthorizationString> for Manifest {
     async fn authorize(&self, auth_token: &TezosAuthorizationString) -> Result<()> {
+        // `pkh` is just a field parsed out of the (as yet unverified)
+        // Authorization header, so it must not be trusted with anything that
+        // has a cost attached — an outbound explorer lookup, a cache entry —
+        // until the signature below, and the check that `pkh` is the address
+        // of the signing key, confirm its bearer actually controls it.
+        // Otherwise an anonymous caller could force unbounded lookups and
+        // cache entries with made-up `pkh` values alone, the same class of
+        // bug the replay-cache-before-verify fix addressed.
+        auth_token.verify()?;
+        if ssi::blakesig::hash_public_key(&jwk_from_tezos_key(&auth_token.pk)?)? != auth_token.pkh {
+            return Err(anyhow!("pkh does not match the signing key"));
+        }
+
         let requester = DIDURL {
             did: format!("did:pkh:tz:{}", &auth_token.pkh),
             fragment: Some("TezosMethod2021".to_string()),
             ..Default::default()
         };
 
-        if !self.invokers().contains(&requester) {
-            Err(anyhow!("Requester not a controller of the orbit"))
-        } else {
-            auth_token.verify()
+        // Prefer the on-chain did:tz document when a resolver is configured for
+        // this orbit, so rotated keys or delegated controllers registered in
+        // the account's DID manifest contract are recognised too. With no
+        // resolver configured, or nothing published on-chain, this falls back
+        // to the locally-derived did:pkh method, as before.
+        let authorized = match self.tzkt_resolver() {
+            Some(resolver) => {
+                self.invokers().contains(&requester)
+                    || resolver
+                        .resolve(&auth_token.pkh)
+                        .await
+                        .iter()
+                        .any(|method| self.invokers().contains(method))
+            }
+            None => self.invokers().contains(&requester),
+        };
+
+        if !authorized {
+            return Err(anyhow!("Requester not a controller of the orbit"));
+        }
+
+        // Reject stale or previously-seen tokens so a captured Authorization
+        // header can't be replayed indefinitely: the signed `timestamp` must
+        // fall within the configured freshness window, and the signature must
+        // not already be in the replay cache for that window. This runs after
+        // `verify()` succeeds: recording a signature as seen before it's been
+        // cryptographically checked would let a forged request (same sig,
+        // some other field tweaked) burn the nonce of a legitimate token that
+        // was never actually accepted.
+        if let Some(guard) = self.replay_guard() {
+            let timestamp = DateTime::parse_from_rfc3339(&auth_token.timestamp)?.with_timezone(&Utc);
+            guard.check(&auth_token.sig, timestamp, Utc::now())?;
         }
+
+        Ok(())
     }
 }
 
@@ -255,6 +297,140 @@ async fn simple_verify_succeed() {
     tza.verify().unwrap();
 }
 
+#[cfg(test)]
+fn compressed_ec_public_key(x: &[u8], y: &[u8]) -> Vec<u8> {
+    let parity = if y[y.len() - 1] % 2 == 0 { 0x02 } else { 0x03 };
+    let mut out = vec![parity];
+    out.extend_from_slice(x);
+    out
+}
+
+#[test]
+async fn round_trip_secp256k1() {
+    use didkit::DID_METHODS;
+    use ssi::{
+        did::Source,
+        jwk::{Algorithm, Params, JWK},
+    };
+
+    let ts = "2021-01-14T15:16:04Z";
+    let dummy_cid = "uAYAEHiB0uGRNPXEMdA9L-lXR2MKIZzKlgW1z6Ug4fSv3LRSPfQ";
+    let dummy_orbit = "kepler:did:example://my-orbit";
+    let j = JWK::generate_secp256k1().unwrap();
+    let did = DID_METHODS
+        .generate(&Source::KeyAndPattern(&j, "tz"))
+        .unwrap();
+    let pkh = did.split(':').last().unwrap();
+    let pk: String = match &j.params {
+        Params::EC(p) => bs58::encode(
+            [3, 254, 226, 86]
+                .iter()
+                .chain(&compressed_ec_public_key(
+                    &p.x_coordinate.as_ref().unwrap().0,
+                    &p.y_coordinate.as_ref().unwrap().0,
+                ))
+                .copied()
+                .collect::<Vec<u8>>(),
+        )
+        .with_check()
+        .into_string(),
+        _ => panic!(),
+    };
+    let tz_unsigned = TezosAuthorizationString {
+        sig: "".into(),
+        domain: "kepler.net".into(),
+        pk,
+        pkh: pkh.into(),
+        timestamp: ts.into(),
+        orbit: dummy_orbit.parse().unwrap(),
+        action: Action::Put(vec![dummy_cid.to_string()]),
+    };
+    let message = tz_unsigned
+        .serialize_for_verification()
+        .expect("failed to serialize authz message");
+    let sig_bytes = ssi::jws::sign_bytes(Algorithm::ESBlake2bK, &message, &j).unwrap();
+    let sig = bs58::encode(
+        [13, 115, 101, 19, 63]
+            .iter()
+            .chain(&sig_bytes)
+            .copied()
+            .collect::<Vec<u8>>(),
+    )
+    .with_check()
+    .into_string();
+    let tz = TezosAuthorizationString { sig, ..tz_unsigned };
+
+    assert_eq!(
+        message,
+        tz.serialize_for_verification()
+            .expect("failed to serialize authz message")
+    );
+    assert!(tz.verify().is_ok());
+}
+
+#[test]
+async fn round_trip_p256() {
+    use didkit::DID_METHODS;
+    use ssi::{
+        did::Source,
+        jwk::{Algorithm, Params, JWK},
+    };
+
+    let ts = "2021-01-14T15:16:04Z";
+    let dummy_cid = "uAYAEHiB0uGRNPXEMdA9L-lXR2MKIZzKlgW1z6Ug4fSv3LRSPfQ";
+    let dummy_orbit = "kepler:did:example://my-orbit";
+    let j = JWK::generate_p256().unwrap();
+    let did = DID_METHODS
+        .generate(&Source::KeyAndPattern(&j, "tz"))
+        .unwrap();
+    let pkh = did.split(':').last().unwrap();
+    let pk: String = match &j.params {
+        Params::EC(p) => bs58::encode(
+            [3, 178, 139, 127]
+                .iter()
+                .chain(&compressed_ec_public_key(
+                    &p.x_coordinate.as_ref().unwrap().0,
+                    &p.y_coordinate.as_ref().unwrap().0,
+                ))
+                .copied()
+                .collect::<Vec<u8>>(),
+        )
+        .with_check()
+        .into_string(),
+        _ => panic!(),
+    };
+    let tz_unsigned = TezosAuthorizationString {
+        sig: "".into(),
+        domain: "kepler.net".into(),
+        pk,
+        pkh: pkh.into(),
+        timestamp: ts.into(),
+        orbit: dummy_orbit.parse().unwrap(),
+        action: Action::Put(vec![dummy_cid.to_string()]),
+    };
+    let message = tz_unsigned
+        .serialize_for_verification()
+        .expect("failed to serialize authz message");
+    let sig_bytes = ssi::jws::sign_bytes(Algorithm::ESBlake2b, &message, &j).unwrap();
+    let sig = bs58::encode(
+        [54, 240, 44, 52]
+            .iter()
+            .chain(&sig_bytes)
+            .copied()
+            .collect::<Vec<u8>>(),
+    )
+    .with_check()
+    .into_string();
+    let tz = TezosAuthorizationString { sig, ..tz_unsigned };
+
+    assert_eq!(
+        message,
+        tz.serialize_for_verification()
+            .expect("failed to serialize authz message")
+    );
+    assert!(tz.verify().is_ok());
+}
+
 #[test]
 async fn round_trip() {
     use didkit::DID_METHODS;
@@ -0,0 +1,195 @@
+use anyhow::Result;
+use serde::Deserialize;
+use ssi::did::DIDURL;
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+use url::Url;
+
+#[derive(Debug, Clone, Deserialize)]
+struct TzKtDidManifest {
+    #[serde(default)]
+    controllers: Vec<String>,
+}
+
+struct CacheEntry {
+    fetched_at: Instant,
+    controllers: Vec<DIDURL>,
+}
+
+/// Resolves the on-chain `did:tz` document for a tz address from a TzKT-style
+/// block explorer API, so that verification methods registered in the
+/// account's DID manifest contract (rotated keys, additional controllers) are
+/// honored alongside the locally-derived `did:pkh` method.
+///
+/// `pkh` is caller-supplied (see [`crate::tz`]'s `authorize`, which only
+/// resolves *after* the signature checks out and `pkh` is confirmed to be the
+/// signing key's address), but defence in depth still bounds the cache by `capacity`,
+/// the same way [`crate::replay::ReplayGuard`] bounds its own nonce cache.
+pub struct DidTzResolver {
+    client: reqwest::Client,
+    explorer: Url,
+    ttl: Duration,
+    negative_ttl: Duration,
+    capacity: usize,
+    cache: RwLock<HashMap<String, CacheEntry>>,
+}
+
+impl DidTzResolver {
+    /// `negative_ttl` should be much shorter than `ttl`: it bounds how long a
+    /// transient explorer outage (or an account with no DID manifest yet) can
+    /// keep a rotated-key controller locked out, whereas `ttl` only needs to
+    /// be refreshed once the on-chain document actually changes. `capacity`
+    /// bounds the number of distinct `pkh`s cached at once; once full, the
+    /// oldest entry is evicted to make room, mirroring `ReplayGuard`.
+    pub fn new(explorer: Url, ttl: Duration, negative_ttl: Duration, capacity: usize) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            explorer,
+            ttl,
+            negative_ttl,
+            capacity,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Verification methods published on-chain for `pkh`. Returns an empty
+    /// list (never an error) when the account has no DID manifest contract
+    /// registered or the explorer can't be reached, so callers can fall back
+    /// to the locally-derived `did:pkh` method.
+    pub async fn resolve(&self, pkh: &str) -> Vec<DIDURL> {
+        if let Some(cached) = self.cached(pkh) {
+            return cached;
+        }
+        let controllers = self.fetch(pkh).await.unwrap_or_default();
+        self.store(pkh, controllers.clone());
+        controllers
+    }
+
+    /// Inserts a freshly-fetched result, evicting the oldest entry first if
+    /// the cache is already at `capacity` (and `pkh` isn't already cached).
+    fn store(&self, pkh: &str, controllers: Vec<DIDURL>) {
+        let mut cache = self.cache.write().unwrap();
+        if !cache.contains_key(pkh) && cache.len() >= self.capacity {
+            if let Some(oldest) = cache
+                .iter()
+                .min_by_key(|(_, e)| e.fetched_at)
+                .map(|(k, _)| k.clone())
+            {
+                cache.remove(&oldest);
+            }
+        }
+        cache.insert(
+            pkh.to_string(),
+            CacheEntry {
+                fetched_at: Instant::now(),
+                controllers,
+            },
+        );
+    }
+
+    fn cached(&self, pkh: &str) -> Option<Vec<DIDURL>> {
+        let cache = self.cache.read().unwrap();
+        let entry = cache.get(pkh)?;
+        // An empty result (no on-chain document found, or the explorer
+        // couldn't be reached) is cached for `negative_ttl` rather than the
+        // full `ttl`, so a transient outage doesn't lock a rotated-key
+        // controller out for the whole positive-result window.
+        let ttl = if entry.controllers.is_empty() {
+            self.negative_ttl
+        } else {
+            self.ttl
+        };
+        if entry.fetched_at.elapsed() < ttl {
+            Some(entry.controllers.clone())
+        } else {
+            None
+        }
+    }
+
+    async fn fetch(&self, pkh: &str) -> Result<Vec<DIDURL>> {
+        let url = self.explorer.join(&format!("v1/accounts/{}/did", pkh))?;
+        let manifest: TzKtDidManifest = self.client.get(url).send().await?.json().await?;
+        manifest
+            .controllers
+            .into_iter()
+            .map(|c| DIDURL::from_str(&c).map_err(|e| anyhow!("Invalid controller DID URL: {}", e)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+fn test_resolver(ttl: Duration, negative_ttl: Duration) -> DidTzResolver {
+    test_resolver_with_capacity(ttl, negative_ttl, 100)
+}
+
+#[cfg(test)]
+fn test_resolver_with_capacity(ttl: Duration, negative_ttl: Duration, capacity: usize) -> DidTzResolver {
+    DidTzResolver::new(
+        Url::parse("http://example.invalid").unwrap(),
+        ttl,
+        negative_ttl,
+        capacity,
+    )
+}
+
+#[test]
+fn positive_result_is_reused_within_ttl() {
+    let resolver = test_resolver(Duration::from_millis(50), Duration::from_millis(5));
+    resolver.cache.write().unwrap().insert(
+        "tz1test".into(),
+        CacheEntry {
+            fetched_at: Instant::now(),
+            controllers: vec![DIDURL::from_str("did:pkh:tz:tz1test#TezosMethod2021").unwrap()],
+        },
+    );
+    assert!(resolver.cached("tz1test").is_some());
+}
+
+#[test]
+fn positive_result_expires_after_ttl() {
+    let resolver = test_resolver(Duration::from_millis(10), Duration::from_millis(5));
+    resolver.cache.write().unwrap().insert(
+        "tz1test".into(),
+        CacheEntry {
+            fetched_at: Instant::now(),
+            controllers: vec![DIDURL::from_str("did:pkh:tz:tz1test#TezosMethod2021").unwrap()],
+        },
+    );
+    std::thread::sleep(Duration::from_millis(20));
+    assert!(resolver.cached("tz1test").is_none());
+}
+
+#[test]
+fn empty_result_expires_sooner_than_a_positive_one_would() {
+    let resolver = test_resolver(Duration::from_millis(200), Duration::from_millis(5));
+    resolver.cache.write().unwrap().insert(
+        "tz1nodoc".into(),
+        CacheEntry {
+            fetched_at: Instant::now(),
+            controllers: vec![],
+        },
+    );
+    std::thread::sleep(Duration::from_millis(20));
+    // Well within the 200ms positive ttl, but past the 5ms negative_ttl: an
+    // empty/failed lookup must not be pinned for the full positive window.
+    assert!(resolver.cached("tz1nodoc").is_none());
+}
+
+#[test]
+fn evicts_oldest_entry_once_full() {
+    let resolver = test_resolver_with_capacity(Duration::from_secs(60), Duration::from_secs(60), 1);
+    resolver.store("tz1first", vec![]);
+    std::thread::sleep(Duration::from_millis(5));
+    resolver.store("tz1second", vec![]);
+
+    // Capacity is 1, so the second distinct pkh must evict the first rather
+    // than growing the cache unboundedly.
+    let cache = resolver.cache.read().unwrap();
+    assert_eq!(cache.len(), 1);
+    assert!(!cache.contains_key("tz1first"));
+    assert!(cache.contains_key("tz1second"));
+}
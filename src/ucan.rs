@@ -0,0 +1,516 @@
+use crate::{
+    auth::{Action, AuthorizationPolicy, AuthorizationToken},
+    jws::JwsSignatureAlgorithm,
+    manifest::Manifest,
+    resource::OrbitId,
+};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rocket::{
+    mtls::Certificate,
+    request::{FromRequest, Outcome, Request},
+};
+use serde::{Deserialize, Serialize};
+use ssi::{
+    did::DIDURL,
+    jwk::{Base64urlUInt, OctetParams, Params, JWK},
+};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct UcanHeader {
+    alg: String,
+    typ: String,
+    ucv: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Caveat {
+    #[serde(default)]
+    content: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Attenuation {
+    with: String,
+    can: String,
+    #[serde(default)]
+    nb: Option<Caveat>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct UcanPayload {
+    iss: String,
+    aud: String,
+    #[serde(default)]
+    nbf: Option<i64>,
+    #[serde(default)]
+    exp: Option<i64>,
+    att: Vec<Attenuation>,
+    #[serde(default)]
+    prf: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Ucan {
+    raw: String,
+    header: UcanHeader,
+    payload: UcanPayload,
+    signature: Vec<u8>,
+    action: Action,
+    orbit: OrbitId,
+    // The identity the transport itself authenticated for whoever is
+    // presenting this request, read from the mTLS client certificate by
+    // `FromRequest` (never from anything the client merely asserts in a
+    // header). A bare `.parse()` leaves this `None`, since a UCAN string
+    // alone carries no proof that its bearer is the party it was delegated
+    // to.
+    invoker: Option<String>,
+}
+
+impl FromStr for Ucan {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, '.');
+        let header_b64 = parts.next().ok_or_else(|| anyhow!("Missing UCAN header"))?;
+        let payload_b64 = parts
+            .next()
+            .ok_or_else(|| anyhow!("Missing UCAN payload"))?;
+        let sig_b64 = parts
+            .next()
+            .ok_or_else(|| anyhow!("Missing UCAN signature"))?;
+
+        let header: UcanHeader = serde_json::from_slice(&base64::decode_config(
+            header_b64,
+            base64::URL_SAFE_NO_PAD,
+        )?)?;
+        if header.typ != "JWT" {
+            return Err(anyhow!("Unsupported UCAN header typ"));
+        }
+        let payload: UcanPayload = serde_json::from_slice(&base64::decode_config(
+            payload_b64,
+            base64::URL_SAFE_NO_PAD,
+        )?)?;
+        let signature = base64::decode_config(sig_b64, base64::URL_SAFE_NO_PAD)?;
+
+        let (orbit, action) = effective_capability(&payload.att)?;
+
+        Ok(Ucan {
+            raw: s.into(),
+            header,
+            payload,
+            signature,
+            action,
+            orbit,
+            invoker: None,
+        })
+    }
+}
+
+fn effective_capability(att: &[Attenuation]) -> Result<(OrbitId, Action)> {
+    let granted = att
+        .iter()
+        .find(|a| a.with.starts_with("kepler:"))
+        .ok_or_else(|| anyhow!("UCAN does not attenuate a kepler resource"))?;
+    let orbit: OrbitId = granted.with.parse()?;
+    let content = granted
+        .nb
+        .as_ref()
+        .map(|nb| nb.content.clone())
+        .unwrap_or_default();
+    let action = match granted.can.as_str() {
+        "kv/put" => Action::Put(content),
+        "kv/get" => Action::Get(content),
+        "kv/del" => Action::Del(content),
+        "kv/list" => Action::List,
+        "kv/create" => Action::Create { content },
+        other => return Err(anyhow!("Unsupported ability: {}", other)),
+    };
+    Ok((orbit, action))
+}
+
+fn can_covers(parent_can: &str, child_can: &str) -> bool {
+    if parent_can == child_can {
+        return true;
+    }
+    match parent_can.strip_suffix("/*") {
+        Some(namespace) => child_can
+            .strip_prefix(namespace)
+            .map(|rest| rest.starts_with('/'))
+            .unwrap_or(false),
+        None => false,
+    }
+}
+
+// A caveat with no `nb.content` (or an empty one) is unrestricted; otherwise
+// the child's content list must be entirely contained in the parent's.
+fn nb_content_covers(parent: &Option<Caveat>, child: &Option<Caveat>) -> bool {
+    let parent_content = match parent {
+        Some(p) if !p.content.is_empty() => &p.content[..],
+        _ => return true,
+    };
+    match child {
+        Some(c) if !c.content.is_empty() => c.content.iter().all(|cid| parent_content.contains(cid)),
+        _ => false,
+    }
+}
+
+fn is_attenuation_of(child: &[Attenuation], parent: &[Attenuation]) -> bool {
+    child.iter().all(|c| {
+        parent.iter().any(|p| {
+            c.with == p.with && can_covers(&p.can, &c.can) && nb_content_covers(&p.nb, &c.nb)
+        })
+    })
+}
+
+fn jwk_from_issuer(did: &str) -> Result<(JWK, JwsSignatureAlgorithm)> {
+    let method_specific_id = did
+        .strip_prefix("did:key:")
+        .ok_or_else(|| anyhow!("Unsupported UCAN issuer method, expected did:key"))?;
+    let (_, bytes) = multibase::decode(method_specific_id)?;
+    match bytes.as_slice() {
+        [codec @ 0xed, 0x01, key @ ..] => Ok((
+            JWK {
+                params: Params::OKP(OctetParams {
+                    curve: "Ed25519".to_string(),
+                    public_key: Base64urlUInt(key.to_vec()),
+                    private_key: None,
+                }),
+                public_key_use: None,
+                key_operations: None,
+                key_id: None,
+                algorithm: None,
+                x509_url: None,
+                x509_certificate_chain: None,
+                x509_thumbprint_sha1: None,
+                x509_thumbprint_sha256: None,
+            },
+            JwsSignatureAlgorithm::from_did_key_codec(*codec)?,
+        )),
+        _ => Err(anyhow!("Unsupported did:key codec")),
+    }
+}
+
+impl Ucan {
+    fn signing_input(&self) -> &str {
+        match self.raw.rfind('.') {
+            Some(i) => &self.raw[..i],
+            None => &self.raw,
+        }
+    }
+
+    fn verify_signature(&self) -> Result<()> {
+        let (key, alg) = jwk_from_issuer(&self.payload.iss)?;
+        alg.verify(self.signing_input().as_bytes(), &key, &self.signature)
+    }
+
+    fn verify_time_bounds(&self, now: DateTime<Utc>) -> Result<()> {
+        let ts = now.timestamp();
+        if let Some(nbf) = self.payload.nbf {
+            if ts < nbf {
+                return Err(anyhow!("UCAN is not yet valid"));
+            }
+        }
+        if let Some(exp) = self.payload.exp {
+            if ts >= exp {
+                return Err(anyhow!("UCAN has expired"));
+            }
+        }
+        Ok(())
+    }
+
+    fn proofs(&self) -> Result<Vec<Ucan>> {
+        self.payload.prf.iter().map(|p| p.parse()).collect()
+    }
+}
+
+// The common name of an mTLS client certificate, used as the invoker's
+// identity. Unlike a request header, this is something the transport itself
+// has already authenticated by the time Rocket hands us the certificate.
+fn invoker_from_certificate(cert: &Certificate<'_>) -> Result<String> {
+    cert.subject()
+        .iter_common_name()
+        .next()
+        .and_then(|cn| cn.as_str().ok())
+        .map(String::from)
+        .ok_or_else(|| anyhow!("Client certificate has no common name to use as invoker"))
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Ucan {
+    type Error = anyhow::Error;
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        // The transport (mutual TLS) is responsible for authenticating who is
+        // actually making this request; without that, a bearer UCAN proves
+        // only that its issuer signed it, not that its bearer is who it was
+        // delegated to. A missing or unparsable certificate just leaves the
+        // invoker unset, which `authorize()` already rejects.
+        let invoker = match request.guard::<Certificate<'_>>().await {
+            Outcome::Success(cert) => invoker_from_certificate(&cert).ok(),
+            _ => None,
+        };
+        match request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .map(Self::from_str)
+        {
+            Some(Ok(mut t)) => {
+                t.invoker = invoker;
+                Outcome::Success(t)
+            }
+            _ => Outcome::Forward(()),
+        }
+    }
+}
+
+impl AuthorizationToken for Ucan {
+    fn action(&self) -> &Action {
+        &self.action
+    }
+    fn target_orbit(&self) -> &OrbitId {
+        &self.orbit
+    }
+}
+
+impl core::fmt::Display for Ucan {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+// Walk the delegation chain backing `token`, verifying each link's signature and
+// time bounds, that each link only narrows (never widens) the capability of its
+// parent, and that the chain is unbroken (a link's issuer must be the audience
+// of the proof directly below it). The chain bottoms out once a link carries no
+// further proofs, at which point its issuer must be a controller of the orbit.
+fn verify_link(token: &Ucan, now: DateTime<Utc>, manifest: &Manifest) -> Result<()> {
+    token.verify_signature()?;
+    token.verify_time_bounds(now)?;
+
+    let proofs = token.proofs()?;
+    if proofs.is_empty() {
+        let root = DIDURL {
+            did: token.payload.iss.clone(),
+            ..Default::default()
+        };
+        return if manifest.invokers().contains(&root) {
+            Ok(())
+        } else {
+            Err(anyhow!("Chain root issuer is not a controller of the orbit"))
+        };
+    }
+
+    for proof in &proofs {
+        if !is_attenuation_of(&token.payload.att, &proof.payload.att) {
+            return Err(anyhow!(
+                "Attenuation is not a subset of the delegated capability"
+            ));
+        }
+        if proof.payload.aud != token.payload.iss {
+            return Err(anyhow!("Delegation chain is broken"));
+        }
+        verify_link(proof, now, manifest)?;
+    }
+    Ok(())
+}
+
+#[rocket::async_trait]
+impl AuthorizationPolicy<Ucan> for Manifest {
+    async fn authorize(&self, auth_token: &Ucan) -> Result<()> {
+        // Requirement (4): the innermost `aud` (this token's own, since it is
+        // the invocation presented in the request) must name whoever is
+        // actually invoking the request, not merely whoever happens to hold
+        // a copy of the token.
+        let invoker = auth_token
+            .invoker
+            .as_deref()
+            .ok_or_else(|| anyhow!("No invoker identity established for this request"))?;
+        if invoker != auth_token.payload.aud {
+            return Err(anyhow!(
+                "UCAN audience does not match the request invoker"
+            ));
+        }
+
+        verify_link(auth_token, Utc::now(), self)
+    }
+}
+
+#[test]
+async fn parse_simple() {
+    // header: {"alg":"EdDSA","typ":"JWT","ucv":"0.9.0"}
+    // payload: {"iss":"did:key:z6MkiVpQ6PFJ9fkCmbKzhRDyDP1jxaH3ktWd3poNcSCWqZzR","aud":"did:key:z6MkiVpQ6PFJ9fkCmbKzhRDyDP1jxaH3ktWd3poNcSCWqZzR","att":[{"with":"kepler:did:example://my-orbit","can":"kv/put"}]}
+    let jwt = "eyJhbGciOiJFZERTQSIsInR5cCI6IkpXVCIsInVjdiI6IjAuOS4wIn0.eyJpc3MiOiJkaWQ6a2V5Ono2TWtpVnBRNlBGSjlma0NtYkt6aFJEeURQMWp4YUgza3RXZDNwb05jU0NXcVp6UiIsImF1ZCI6ImRpZDprZXk6ejZNa2lWcFE2UEZKOWZrQ21iS3poUkR5RFAxanhhSDNrdFdkM3BvTmNTQ1dxWnpSIiwiYXR0IjpbeyJ3aXRoIjoia2VwbGVyOmRpZDpleGFtcGxlOi8vbXktb3JiaXQiLCJjYW4iOiJrdi9wdXQifV19.sig";
+    let ucan: Ucan = jwt.parse().unwrap();
+    assert_eq!(ucan.payload.att[0].can, "kv/put");
+    assert!(matches!(ucan.action, Action::Put(_)));
+}
+
+#[test]
+async fn attenuation_subset() {
+    let parent = vec![Attenuation {
+        with: "kepler:did:example://my-orbit".into(),
+        can: "kv/*".into(),
+        nb: None,
+    }];
+    let narrower = vec![Attenuation {
+        with: "kepler:did:example://my-orbit".into(),
+        can: "kv/put".into(),
+        nb: None,
+    }];
+    let unrelated = vec![Attenuation {
+        with: "kepler:did:example://other-orbit".into(),
+        can: "kv/put".into(),
+        nb: None,
+    }];
+    assert!(is_attenuation_of(&narrower, &parent));
+    assert!(!is_attenuation_of(&unrelated, &parent));
+}
+
+#[test]
+async fn attenuation_subset_respects_nb_content() {
+    let parent = vec![Attenuation {
+        with: "kepler:did:example://my-orbit".into(),
+        can: "kv/put".into(),
+        nb: Some(Caveat {
+            content: vec!["cid1".into()],
+        }),
+    }];
+    let narrower = vec![Attenuation {
+        with: "kepler:did:example://my-orbit".into(),
+        can: "kv/put".into(),
+        nb: Some(Caveat {
+            content: vec!["cid1".into()],
+        }),
+    }];
+    let widened = vec![Attenuation {
+        with: "kepler:did:example://my-orbit".into(),
+        can: "kv/put".into(),
+        nb: Some(Caveat {
+            content: vec!["cid1".into(), "cid2".into()],
+        }),
+    }];
+    assert!(is_attenuation_of(&narrower, &parent));
+    assert!(!is_attenuation_of(&widened, &parent));
+}
+
+#[cfg(test)]
+fn did_key_from_ed25519(public_key: &[u8]) -> String {
+    let mut bytes = vec![0xed, 0x01];
+    bytes.extend_from_slice(public_key);
+    format!(
+        "did:key:{}",
+        multibase::encode(multibase::Base::Base58Btc, bytes)
+    )
+}
+
+#[cfg(test)]
+fn encode_ucan(payload: &serde_json::Value, key: &JWK) -> String {
+    use ssi::jwk::Algorithm;
+
+    let header = serde_json::json!({"alg": "EdDSA", "typ": "JWT", "ucv": "0.9.0"});
+    let header_b64 = base64::encode_config(header.to_string(), base64::URL_SAFE_NO_PAD);
+    let payload_b64 = base64::encode_config(payload.to_string(), base64::URL_SAFE_NO_PAD);
+    let signing_input = format!("{}.{}", header_b64, payload_b64);
+    let sig = ssi::jws::sign_bytes(Algorithm::EdDSA, signing_input.as_bytes(), key).unwrap();
+    let sig_b64 = base64::encode_config(sig, base64::URL_SAFE_NO_PAD);
+    format!("{}.{}", signing_input, sig_b64)
+}
+
+#[cfg(test)]
+fn test_ed25519_did() -> (JWK, String) {
+    let key = JWK::generate_ed25519().unwrap();
+    let public_key = match &key.params {
+        Params::OKP(p) => p.public_key.0.clone(),
+        _ => panic!(),
+    };
+    let did = did_key_from_ed25519(&public_key);
+    (key, did)
+}
+
+#[test]
+async fn full_chain_verifies() {
+    use crate::manifest::ManifestConfig;
+    use std::collections::HashSet;
+
+    let (controller_key, controller_did) = test_ed25519_did();
+    let (delegate_key, delegate_did) = test_ed25519_did();
+
+    let delegation = encode_ucan(
+        &serde_json::json!({
+            "iss": controller_did,
+            "aud": delegate_did,
+            "att": [{"with": "kepler:did:example://my-orbit", "can": "kv/*"}],
+        }),
+        &controller_key,
+    );
+    let invocation = encode_ucan(
+        &serde_json::json!({
+            "iss": delegate_did,
+            "aud": delegate_did,
+            "att": [{"with": "kepler:did:example://my-orbit", "can": "kv/put"}],
+            "prf": [delegation],
+        }),
+        &delegate_key,
+    );
+
+    let mut token: Ucan = invocation.parse().unwrap();
+    token.invoker = Some(delegate_did.clone());
+
+    let mut invokers = HashSet::new();
+    invokers.insert(DIDURL {
+        did: controller_did,
+        ..Default::default()
+    });
+    let manifest = Manifest::new(invokers, ManifestConfig::default());
+
+    assert!(manifest.authorize(&token).await.is_ok());
+}
+
+#[test]
+async fn rejects_wrong_invoker_and_widened_delegation() {
+    use crate::manifest::ManifestConfig;
+    use std::collections::HashSet;
+
+    let (controller_key, controller_did) = test_ed25519_did();
+    let (delegate_key, delegate_did) = test_ed25519_did();
+    let (_, imposter_did) = test_ed25519_did();
+
+    // The delegation only grants `kv/get`, but the invocation (forwarded by
+    // someone other than the delegate) claims `kv/put` and names an aud that
+    // doesn't match the invoker the transport established.
+    let delegation = encode_ucan(
+        &serde_json::json!({
+            "iss": controller_did,
+            "aud": delegate_did,
+            "att": [{"with": "kepler:did:example://my-orbit", "can": "kv/get"}],
+        }),
+        &controller_key,
+    );
+    let invocation = encode_ucan(
+        &serde_json::json!({
+            "iss": delegate_did,
+            "aud": delegate_did,
+            "att": [{"with": "kepler:did:example://my-orbit", "can": "kv/put"}],
+            "prf": [delegation],
+        }),
+        &delegate_key,
+    );
+
+    let mut token: Ucan = invocation.parse().unwrap();
+    token.invoker = Some(imposter_did);
+
+    let mut invokers = HashSet::new();
+    invokers.insert(DIDURL {
+        did: controller_did,
+        ..Default::default()
+    });
+    let manifest = Manifest::new(invokers, ManifestConfig::default());
+
+    assert!(manifest.authorize(&token).await.is_err());
+
+    // With the invoker the transport established matching `aud`, the
+    // widened `kv/put` over a `kv/get` delegation is still rejected.
+    token.invoker = Some(delegate_did);
+    assert!(manifest.authorize(&token).await.is_err());
+}